@@ -1,16 +1,26 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::{Hash, Hasher};
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
 use extract_frontmatter::Extractor;
 use handlebars::Handlebars;
 use log::{error, info};
 use maplit::{btreemap, hashmap};
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
 use serde_derive::{Deserialize, Serialize};
 use structopt::StructOpt;
 use tempfile::NamedTempFile;
 use tokio::process::Command;
 
+const SYNC_MANIFEST_FILE: &str = ".uwiki-sync.toml";
+const KEYRING_SERVICE: &str = "uwiki-cli";
+
 fn default_address() -> String {
     "http://localhost:1181".to_string()
 }
@@ -28,14 +38,51 @@ struct Config {
     username: Option<String>,
     password: Option<String>,
 
-    token: Option<String>,
+    media_address: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SyncManifest {
+    #[serde(default)]
+    pages: BTreeMap<String, SyncManifestEntry>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SyncManifestEntry {
+    version: i32,
+    body_hash: u64,
 }
 
 #[derive(Debug, StructOpt)]
 enum Subcommand {
     AddUser { username: String, password: String },
     Auth,
-    SetPage { slug: String },
+    SetPage {
+        slug: Option<String>,
+    },
+    List,
+    Sync {
+        dir: PathBuf,
+
+        #[structopt(long)]
+        push: bool,
+
+        #[structopt(long)]
+        pull: bool,
+    },
+    Watch { dir: PathBuf },
+    Upload {
+        file: PathBuf,
+
+        #[structopt(long)]
+        slug: Option<String>,
+
+        #[structopt(long)]
+        max_width: Option<u32>,
+
+        #[structopt(long)]
+        max_height: Option<u32>,
+    },
 }
 
 #[derive(Debug, StructOpt)]
@@ -87,14 +134,50 @@ struct SetPageResponse {
     new_version: Option<i32>,
 }
 
-async fn cmd_add_user(username: String, password: String, config: Config) -> Result<()> {
+#[derive(Debug, Deserialize)]
+struct UploadResponse {
+    success: bool,
+    message: String,
+    url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PageSummary {
+    slug: String,
+    title: String,
+    version: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListPagesResponse {
+    success: bool,
+    message: String,
+    pages: Option<Vec<PageSummary>>,
+}
+
+fn token_keyring_entry(config: &Config) -> keyring::Entry {
+    keyring::Entry::new(KEYRING_SERVICE, &config.server_address)
+}
+
+fn looks_like_auth_failure(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("token") || message.contains("auth")
+}
+
+async fn reauthenticate(config: &Config) -> Result<String> {
     let map = hashmap! {
-        "username" => username,
-        "password" => password,
+        "username" => config
+            .username
+            .clone()
+            .ok_or_else(|| anyhow!("config is missing username"))?,
+        "password" => config
+            .password
+            .clone()
+            .ok_or_else(|| anyhow!("config is missing password"))?,
     };
 
-    let response: AddUserResponse = reqwest::Client::new()
-        .post(format!("{}/u", config.server_address))
+    let response: AuthenticateResponse = reqwest::Client::new()
+        .post(format!("{}/a", config.server_address))
         .json(&map)
         .send()
         .await
@@ -103,28 +186,354 @@ async fn cmd_add_user(username: String, password: String, config: Config) -> Res
         .await
         .context("error parsing response JSON")?;
 
-    if response.success {
-        info!("{}", response.message);
+    if !response.success {
+        return Err(anyhow!(response.message));
+    }
+
+    response
+        .token
+        .ok_or_else(|| anyhow!("Auth was successful, but no token was returned"))
+}
+
+async fn get_or_refresh_token(config: &Config, force_refresh: bool) -> Result<String> {
+    let entry = token_keyring_entry(config);
+
+    if !force_refresh {
+        if let Ok(token) = entry.get_password() {
+            return Ok(token);
+        }
+    }
+
+    let token = reauthenticate(config).await?;
+    entry
+        .set_password(&token)
+        .context("failed to store token in keyring")?;
+
+    Ok(token)
+}
+
+async fn get_page(client: &reqwest::Client, config: &Config, slug: &str) -> Result<GetPageResponse> {
+    let mut token = get_or_refresh_token(config, false).await?;
+    let mut retried = false;
+
+    loop {
+        let map = hashmap! {
+            "token" => token.clone(),
+            "slug" => slug.to_string(),
+        };
+
+        let response: GetPageResponse = client
+            .post(format!("{}/g", config.server_address))
+            .json(&map)
+            .send()
+            .await
+            .context("error sending request")?
+            .json()
+            .await
+            .context("error parsing response JSON")?;
+
+        if !retried && !response.success && looks_like_auth_failure(&response.message) {
+            token = get_or_refresh_token(config, true).await?;
+            retried = true;
+            continue;
+        }
+
+        return Ok(response);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn set_page(
+    client: &reqwest::Client,
+    config: &Config,
+    slug: &str,
+    title: &str,
+    body: &str,
+    previous_version: i32,
+) -> Result<SetPageResponse> {
+    let mut token = get_or_refresh_token(config, false).await?;
+    let mut retried = false;
+
+    loop {
+        let request = SetPageRequest {
+            token: token.clone(),
+            slug: slug.to_string(),
+            title: title.to_string(),
+            body: body.to_string(),
+            previous_version,
+        };
+
+        let response: SetPageResponse = client
+            .post(format!("{}/s", config.server_address))
+            .json(&request)
+            .send()
+            .await
+            .context("error sending request")?
+            .json()
+            .await
+            .context("error parsing response JSON")?;
+
+        if !retried && !response.success && looks_like_auth_failure(&response.message) {
+            token = get_or_refresh_token(config, true).await?;
+            retried = true;
+            continue;
+        }
+
+        return Ok(response);
+    }
+}
+
+async fn list_pages(client: &reqwest::Client, config: &Config) -> Result<ListPagesResponse> {
+    let mut token = get_or_refresh_token(config, false).await?;
+    let mut retried = false;
+
+    loop {
+        let map = hashmap! { "token" => token.clone() };
+
+        let response: ListPagesResponse = client
+            .post(format!("{}/l", config.server_address))
+            .json(&map)
+            .send()
+            .await
+            .context("error sending request")?
+            .json()
+            .await
+            .context("error parsing response JSON")?;
+
+        if !retried && !response.success && looks_like_auth_failure(&response.message) {
+            token = get_or_refresh_token(config, true).await?;
+            retried = true;
+            continue;
+        }
+
+        return Ok(response);
+    }
+}
+
+fn render_page_file(title: Option<String>, body: Option<String>) -> Result<String> {
+    let source = "---\ntitle: {{#if title}}{{title}}{{/if}}\n---\n{{#if body}}{{body}}{{/if}}";
+    let mut handlebars = Handlebars::new();
+    handlebars.register_template_string("t1", source)?;
+
+    let data = btreemap! {
+        "title" => title,
+        "body" => body,
+    };
+
+    Ok(handlebars.render("t1", &data)?)
+}
+
+fn parse_page_file(contents: &str) -> Result<(String, String)> {
+    let mut extractor = Extractor::new(contents);
+    extractor.select_by_terminator("---");
+
+    let (front_matter, body) = extractor.split();
+    let front_matter = front_matter.join("\n");
+    let metadata: PageMetadata = serde_yaml::from_str(&front_matter)?;
+
+    Ok((metadata.title, body.to_string()))
+}
+
+fn hash_body(body: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn load_sync_manifest(dir: &Path) -> Result<SyncManifest> {
+    let path = dir.join(SYNC_MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(SyncManifest::default());
+    }
+
+    let contents = fs::read_to_string(&path)
+        .with_context(|| anyhow!("failed to read sync manifest at {:?}", path))?;
+
+    toml::from_str(&contents)
+        .with_context(|| anyhow!("failed to parse sync manifest at {:?}", path))
+}
+
+fn save_sync_manifest(dir: &Path, manifest: &SyncManifest) -> Result<()> {
+    let path = dir.join(SYNC_MANIFEST_FILE);
+    let contents = toml::to_string_pretty(manifest)?;
+
+    fs::write(&path, contents)
+        .with_context(|| anyhow!("failed to write sync manifest at {:?}", path))
+}
+
+fn looks_like_version_conflict(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("version") || message.contains("conflict")
+}
+
+async fn run_editor(path: &Path) -> Result<bool> {
+    let exit_status = Command::new(std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string()))
+        .arg(path)
+        .spawn()?
+        .wait()
+        .await?;
+
+    Ok(exit_status.success())
+}
+
+/// Finds the longest common subsequence of lines between `base` and `other`,
+/// returning the matched `(base_index, other_index)` pairs in order.
+fn lcs_matches(base: &[&str], other: &[&str]) -> Vec<(usize, usize)> {
+    let (n, m) = (base.len(), other.len());
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if base[i] == other[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut matches = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if base[i] == other[j] {
+            matches.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    matches
+}
+
+/// Appends a single base/mine/theirs region to `output`, emitting conflict
+/// markers only where both sides diverged from `base` in different ways.
+fn emit_merge_region(base: &[&str], mine: &[&str], theirs: &[&str], output: &mut Vec<String>) {
+    if mine == theirs {
+        output.extend(mine.iter().map(|line| line.to_string()));
+    } else if mine == base {
+        output.extend(theirs.iter().map(|line| line.to_string()));
+    } else if theirs == base {
+        output.extend(mine.iter().map(|line| line.to_string()));
     } else {
-        error!("{}", response.message);
+        output.push("<<<<<<< mine".to_string());
+        output.extend(mine.iter().map(|line| line.to_string()));
+        output.push("=======".to_string());
+        output.extend(theirs.iter().map(|line| line.to_string()));
+        output.push(">>>>>>> server".to_string());
     }
+}
 
-    Ok(())
+/// diff3-style merge of `mine` and `theirs`, both derived from `base`.
+fn three_way_merge(base: &str, mine: &str, theirs: &str) -> String {
+    let base_lines: Vec<&str> = base.lines().collect();
+    let mine_lines: Vec<&str> = mine.lines().collect();
+    let theirs_lines: Vec<&str> = theirs.lines().collect();
+
+    let mine_match_by_base: HashMap<usize, usize> =
+        lcs_matches(&base_lines, &mine_lines).into_iter().collect();
+    let theirs_match_by_base: HashMap<usize, usize> =
+        lcs_matches(&base_lines, &theirs_lines).into_iter().collect();
+
+    // Anchors are base lines present unchanged on both sides; they split the
+    // merge into independently resolvable regions.
+    let anchors: Vec<(usize, usize, usize)> = (0..base_lines.len())
+        .filter_map(|bi| {
+            let mi = *mine_match_by_base.get(&bi)?;
+            let ti = *theirs_match_by_base.get(&bi)?;
+            Some((bi, mi, ti))
+        })
+        .collect();
+
+    let mut output: Vec<String> = Vec::new();
+    let (mut bi_cursor, mut mi_cursor, mut ti_cursor) = (0, 0, 0);
+
+    for (bi, mi, ti) in anchors {
+        emit_merge_region(
+            &base_lines[bi_cursor..bi],
+            &mine_lines[mi_cursor..mi],
+            &theirs_lines[ti_cursor..ti],
+            &mut output,
+        );
+        output.push(base_lines[bi].to_string());
+        bi_cursor = bi + 1;
+        mi_cursor = mi + 1;
+        ti_cursor = ti + 1;
+    }
+
+    emit_merge_region(
+        &base_lines[bi_cursor..],
+        &mine_lines[mi_cursor..],
+        &theirs_lines[ti_cursor..],
+        &mut output,
+    );
+
+    output.join("\n")
 }
 
-async fn cmd_auth(config: Config) -> Result<()> {
-    let map = hashmap! {
-        "username" => config
-            .username
-            .ok_or_else(|| anyhow!("config is missing username"))?,
-        "password" => config
-            .password
-            .ok_or_else(|| anyhow!("config is missing password"))?,
+fn resize_image(
+    path: &Path,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> Result<Vec<u8>> {
+    let format = image::ImageFormat::from_path(path)
+        .with_context(|| anyhow!("failed to determine image format for {:?}", path))?;
+
+    let image = image::open(path).with_context(|| anyhow!("failed to decode image at {:?}", path))?;
+
+    let target_width = max_width.unwrap_or_else(|| image.width()).min(image.width());
+    let target_height = max_height.unwrap_or_else(|| image.height()).min(image.height());
+
+    let resized = image.resize(target_width, target_height, image::imageops::FilterType::Lanczos3);
+
+    let mut bytes = Vec::new();
+    resized
+        .write_to(&mut std::io::Cursor::new(&mut bytes), format)
+        .context("failed to re-encode resized image")?;
+
+    Ok(bytes)
+}
+
+async fn cmd_upload(
+    file: PathBuf,
+    slug: Option<String>,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    config: Config,
+) -> Result<()> {
+    let token = get_or_refresh_token(&config, false).await?;
+
+    let file_name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("failed to determine file name for {:?}", file))?
+        .to_string();
+
+    let bytes = if max_width.is_some() || max_height.is_some() {
+        resize_image(&file, max_width, max_height)?
+    } else {
+        fs::read(&file).with_context(|| anyhow!("failed to read file at {:?}", file))?
     };
 
-    let response: AuthenticateResponse = reqwest::Client::new()
-        .post(format!("{}/a", config.server_address))
-        .json(&map)
+    let endpoint = config
+        .media_address
+        .clone()
+        .unwrap_or_else(|| format!("{}/m", config.server_address));
+
+    let mut form = reqwest::multipart::Form::new()
+        .text("token", token)
+        .part("file", reqwest::multipart::Part::bytes(bytes).file_name(file_name));
+
+    if let Some(slug) = slug {
+        form = form.text("slug", slug);
+    }
+
+    let response: UploadResponse = reqwest::Client::new()
+        .post(endpoint)
+        .multipart(form)
         .send()
         .await
         .context("error sending request")?
@@ -133,9 +542,9 @@ async fn cmd_auth(config: Config) -> Result<()> {
         .context("error parsing response JSON")?;
 
     if response.success {
-        match response.token {
-            Some(token) => info!("Set 'token = \"{}\"' in your uwiki-cli config file", token),
-            None => error!("Auth was successful, but no token was returned. Please retry."),
+        match response.url {
+            Some(url) => info!("{}", url),
+            None => info!("{}", response.message),
         }
     } else {
         error!("{}", response.message);
@@ -144,17 +553,14 @@ async fn cmd_auth(config: Config) -> Result<()> {
     Ok(())
 }
 
-async fn cmd_set_page(slug: String, config: Config) -> Result<()> {
-    let token = config
-        .token
-        .ok_or_else(|| anyhow!("No token set in config file"))?;
+async fn cmd_add_user(username: String, password: String, config: Config) -> Result<()> {
     let map = hashmap! {
-        "token" => token.clone(),
-        "slug" => slug.clone(),
+        "username" => username,
+        "password" => password,
     };
 
-    let response: GetPageResponse = reqwest::Client::new()
-        .post(format!("{}/g", config.server_address))
+    let response: AddUserResponse = reqwest::Client::new()
+        .post(format!("{}/u", config.server_address))
         .json(&map)
         .send()
         .await
@@ -163,40 +569,56 @@ async fn cmd_set_page(slug: String, config: Config) -> Result<()> {
         .await
         .context("error parsing response JSON")?;
 
-    if !response.success {
-        return Err(anyhow!("Error getting page from server"));
+    if response.success {
+        info!("{}", response.message);
+    } else {
+        error!("{}", response.message);
     }
 
-    let previous_version = match response.version {
-        Some(version) => version,
-        None => {
-            return Err(anyhow!("Server failed to return page version"));
-        }
-    };
+    Ok(())
+}
 
-    let mut file = NamedTempFile::new()?;
-    let source = "---\ntitle: {{#if title}}{{title}}{{/if}}\n---\n{{#if body}}{{body}}{{/if}}";
-    let mut handlebars = Handlebars::new();
+async fn cmd_auth(config: Config) -> Result<()> {
+    let token = reauthenticate(&config).await?;
 
-    handlebars.register_template_string("t1", source)?;
+    token_keyring_entry(&config)
+        .set_password(&token)
+        .context("failed to store token in keyring")?;
 
-    let data = btreemap! {
-        "title" => response.title,
-        "body" => response.body,
+    info!("Stored a new token for {} in the keyring", config.server_address);
+
+    Ok(())
+}
+
+async fn cmd_set_page(slug: Option<String>, config: Config) -> Result<()> {
+    let client = reqwest::Client::new();
+
+    let (slug, previous_version, base_body, template_title, template_body) = match slug {
+        Some(slug) => {
+            let response = get_page(&client, &config, &slug).await?;
+
+            if !response.success {
+                return Err(anyhow!("Error getting page from server"));
+            }
+
+            let previous_version = response
+                .version
+                .ok_or_else(|| anyhow!("Server failed to return page version"))?;
+            let base_body = response.body.clone().unwrap_or_default();
+
+            (Some(slug), previous_version, base_body, response.title, response.body)
+        }
+        None => (None, 0, String::new(), None, None),
     };
-    let text = handlebars.render("t1", &data)?;
 
-    file.write_all(text.as_bytes())?;
+    let mut file = NamedTempFile::new()?;
+    let text = render_page_file(template_title, template_body)?;
 
-    let exit_status = Command::new(std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string()))
-        .arg(file.path())
-        .spawn()?
-        .wait()
-        .await?;
+    file.write_all(text.as_bytes())?;
 
     // TODO(jsvana): make all errors after editor dump the file
     // and log to user
-    if !exit_status.success() {
+    if !run_editor(file.path()).await? {
         let (_, path) = file.keep()?;
         info!(
             "Editor exited with nonzero code. Refusing to continue. \
@@ -211,30 +633,58 @@ async fn cmd_set_page(slug: String, config: Config) -> Result<()> {
     file.seek(SeekFrom::Start(0))?;
     file.read_to_string(&mut contents)?;
 
-    let mut extractor = Extractor::new(&contents);
-    extractor.select_by_terminator("---");
+    let (title, body) = parse_page_file(&contents)?;
 
-    let (front_matter, body) = extractor.split();
-    let front_matter = front_matter.join("\n");
-    let metadata: PageMetadata = serde_yaml::from_str(&front_matter)?;
+    let slug = slug.unwrap_or_else(|| slug::slugify(&title));
 
-    let request = SetPageRequest {
-        token,
-        slug,
-        title: metadata.title,
-        body: body.to_string(),
-        previous_version,
-    };
+    let response = set_page(&client, &config, &slug, &title, &body, previous_version).await?;
 
-    let response: SetPageResponse = reqwest::Client::new()
-        .post(format!("{}/s", config.server_address))
-        .json(&request)
-        .send()
-        .await
-        .context("error sending request")?
-        .json()
-        .await
-        .context("error parsing response JSON")?;
+    if response.success {
+        info!("{}", response.message);
+        return Ok(());
+    }
+
+    if !looks_like_version_conflict(&response.message) {
+        error!("{}", response.message);
+        return Ok(());
+    }
+
+    info!("Version conflict detected, merging in the latest server changes");
+
+    let latest = get_page(&client, &config, &slug).await?;
+    if !latest.success {
+        return Err(anyhow!("Error getting latest page from server"));
+    }
+
+    let latest_version = latest
+        .version
+        .ok_or_else(|| anyhow!("Server failed to return page version"))?;
+    let theirs_body = latest.body.unwrap_or_default();
+
+    let merged_body = three_way_merge(&base_body, &body, &theirs_body);
+
+    let mut merge_file = NamedTempFile::new()?;
+    let merge_text = render_page_file(Some(title), Some(merged_body))?;
+    merge_file.write_all(merge_text.as_bytes())?;
+
+    if !run_editor(merge_file.path()).await? {
+        let (_, path) = merge_file.keep()?;
+        info!(
+            "Editor exited with nonzero code. Refusing to continue. \
+            Edited content is accessible at \"{}\".",
+            path.display()
+        );
+
+        return Ok(());
+    }
+
+    let mut merged_contents = String::new();
+    merge_file.seek(SeekFrom::Start(0))?;
+    merge_file.read_to_string(&mut merged_contents)?;
+
+    let (title, body) = parse_page_file(&merged_contents)?;
+
+    let response = set_page(&client, &config, &slug, &title, &body, latest_version).await?;
 
     if response.success {
         info!("{}", response.message);
@@ -245,6 +695,223 @@ async fn cmd_set_page(slug: String, config: Config) -> Result<()> {
     Ok(())
 }
 
+async fn cmd_list(config: Config) -> Result<()> {
+    let client = reqwest::Client::new();
+    let response = list_pages(&client, &config).await?;
+
+    if !response.success {
+        error!("{}", response.message);
+        return Ok(());
+    }
+
+    println!("{:<30} {:<40} {:>7}", "SLUG", "TITLE", "VERSION");
+    for page in response.pages.unwrap_or_default() {
+        println!("{:<30} {:<40} {:>7}", page.slug, page.title, page.version);
+    }
+
+    Ok(())
+}
+
+async fn push_page(
+    client: &reqwest::Client,
+    config: &Config,
+    slug: &str,
+    path: &Path,
+    manifest: &mut SyncManifest,
+) -> Result<()> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| anyhow!("failed to read page file at {:?}", path))?;
+    let (title, body) = parse_page_file(&contents)?;
+    let body_hash = hash_body(&body);
+
+    if let Some(entry) = manifest.pages.get(slug) {
+        if entry.body_hash == body_hash {
+            info!("{} is unchanged, skipping", slug);
+            return Ok(());
+        }
+    }
+
+    let previous_version = manifest
+        .pages
+        .get(slug)
+        .map(|entry| entry.version)
+        .unwrap_or(0);
+
+    let response = set_page(client, config, slug, &title, &body, previous_version).await?;
+
+    if response.success {
+        info!("{}: {}", slug, response.message);
+        if let Some(new_version) = response.new_version {
+            manifest.pages.insert(
+                slug.to_string(),
+                SyncManifestEntry {
+                    version: new_version,
+                    body_hash,
+                },
+            );
+        }
+    } else {
+        error!("{}: {}", slug, response.message);
+    }
+
+    Ok(())
+}
+
+async fn pull_page(
+    client: &reqwest::Client,
+    config: &Config,
+    slug: &str,
+    path: &Path,
+    manifest: &mut SyncManifest,
+) -> Result<()> {
+    let response = get_page(client, config, slug).await?;
+
+    if !response.success {
+        error!("{}: {}", slug, response.message);
+        return Ok(());
+    }
+
+    let server_version = match response.version {
+        Some(version) => version,
+        None => {
+            error!("{}: server failed to return page version", slug);
+            return Ok(());
+        }
+    };
+
+    let known_version = manifest.pages.get(slug).map(|entry| entry.version);
+    if known_version == Some(server_version) {
+        return Ok(());
+    }
+
+    let body = response.body.unwrap_or_default();
+    let body_hash = hash_body(&body);
+    let text = render_page_file(response.title, Some(body))?;
+
+    fs::write(path, &text).with_context(|| anyhow!("failed to write page file at {:?}", path))?;
+
+    manifest.pages.insert(
+        slug.to_string(),
+        SyncManifestEntry {
+            version: server_version,
+            body_hash,
+        },
+    );
+
+    info!("{}: pulled version {}", slug, server_version);
+
+    Ok(())
+}
+
+async fn cmd_sync(dir: PathBuf, push: bool, pull: bool, config: Config) -> Result<()> {
+    let mut manifest = load_sync_manifest(&dir)?;
+    let client = reqwest::Client::new();
+
+    if push {
+        for entry in
+            fs::read_dir(&dir).with_context(|| anyhow!("failed to read directory {:?}", dir))?
+        {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                continue;
+            }
+
+            let slug = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| anyhow!("failed to determine slug for {:?}", path))?
+                .to_string();
+
+            push_page(&client, &config, &slug, &path, &mut manifest).await?;
+        }
+    }
+
+    if pull {
+        let slugs: Vec<String> = manifest.pages.keys().cloned().collect();
+        for slug in slugs {
+            let path = dir.join(format!("{}.md", slug));
+            pull_page(&client, &config, &slug, &path, &mut manifest).await?;
+        }
+    }
+
+    save_sync_manifest(&dir, &manifest)?;
+
+    Ok(())
+}
+
+async fn handle_watch_event(
+    client: &reqwest::Client,
+    config: &Config,
+    path: &Path,
+    versions: &mut HashMap<String, i32>,
+) -> Result<()> {
+    if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+        return Ok(());
+    }
+
+    let slug = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| anyhow!("failed to determine slug for {:?}", path))?
+        .to_string();
+
+    let contents = fs::read_to_string(path)
+        .with_context(|| anyhow!("failed to read page file at {:?}", path))?;
+    let (title, body) = parse_page_file(&contents)?;
+
+    let previous_version = match versions.get(&slug) {
+        Some(version) => *version,
+        None => {
+            let response = get_page(client, config, &slug).await?;
+            if !response.success {
+                return Err(anyhow!("error getting page from server"));
+            }
+            response
+                .version
+                .ok_or_else(|| anyhow!("server failed to return page version"))?
+        }
+    };
+
+    let response = set_page(client, config, &slug, &title, &body, previous_version).await?;
+
+    if !response.success {
+        return Err(anyhow!(response.message));
+    }
+
+    if let Some(new_version) = response.new_version {
+        versions.insert(slug, new_version);
+    }
+
+    info!("{}", response.message);
+
+    Ok(())
+}
+
+async fn cmd_watch(dir: PathBuf, config: Config) -> Result<()> {
+    let client = reqwest::Client::new();
+    let mut versions: HashMap<String, i32> = HashMap::new();
+
+    let (tx, rx) = channel();
+    let mut watcher = notify::watcher(tx, Duration::from_millis(500))?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| anyhow!("failed to watch directory {:?}", dir))?;
+
+    info!("Watching {:?} for changes", dir);
+
+    loop {
+        match rx.recv() {
+            Ok(DebouncedEvent::Write(path)) | Ok(DebouncedEvent::Create(path)) => {
+                if let Err(err) = handle_watch_event(&client, &config, &path, &mut versions).await {
+                    error!("{:?}: {:#}", path, err);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => error!("watch error: {:?}", err),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     if std::env::var_os("RUST_LOG").is_none() {
@@ -275,5 +942,14 @@ async fn main() -> Result<()> {
         }
         Subcommand::Auth => cmd_auth(config).await,
         Subcommand::SetPage { slug } => cmd_set_page(slug, config).await,
+        Subcommand::List => cmd_list(config).await,
+        Subcommand::Sync { dir, push, pull } => cmd_sync(dir, push, pull, config).await,
+        Subcommand::Watch { dir } => cmd_watch(dir, config).await,
+        Subcommand::Upload {
+            file,
+            slug,
+            max_width,
+            max_height,
+        } => cmd_upload(file, slug, max_width, max_height, config).await,
     }
 }